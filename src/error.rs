@@ -0,0 +1,67 @@
+//! Error type used throughout the crate.
+
+use std::io;
+use std::fmt;
+use std::error;
+use std::convert::From;
+use serde_json;
+use csv;
+
+#[derive(Debug)]
+pub enum Error {
+    Io(io::Error),
+    Json(serde_json::Error),
+    Csv(csv::Error),
+    /// Malformed input that isn't an io/json/csv error in itself, e.g. a
+    /// streamed file that doesn't contain the structure we expect.
+    Parse(String),
+}
+
+impl fmt::Display for Error {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match *self {
+            Error::Io(ref err) => err.fmt(f),
+            Error::Json(ref err) => err.fmt(f),
+            Error::Csv(ref err) => err.fmt(f),
+            Error::Parse(ref msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+impl error::Error for Error {
+    fn description(&self) -> &str {
+        match *self {
+            Error::Io(ref err) => error::Error::description(err),
+            Error::Json(ref err) => error::Error::description(err),
+            Error::Csv(ref err) => error::Error::description(err),
+            Error::Parse(ref msg) => msg,
+        }
+    }
+
+    fn cause(&self) -> Option<&error::Error> {
+        match *self {
+            Error::Io(ref err) => Some(err),
+            Error::Json(ref err) => Some(err),
+            Error::Csv(ref err) => Some(err),
+            Error::Parse(_) => None,
+        }
+    }
+}
+
+impl From<io::Error> for Error {
+    fn from(err: io::Error) -> Error {
+        Error::Io(err)
+    }
+}
+
+impl From<serde_json::Error> for Error {
+    fn from(err: serde_json::Error) -> Error {
+        Error::Json(err)
+    }
+}
+
+impl From<csv::Error> for Error {
+    fn from(err: csv::Error) -> Error {
+        Error::Csv(err)
+    }
+}
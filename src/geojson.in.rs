@@ -0,0 +1,40 @@
+// GeoJSON structures used to decode the `kommuner.geojson` style files this
+// crate reads. Kept separate from lib.rs so the `serde_codegen` build-script
+// path (see build.rs) can generate the same types out-of-tree on stable.
+
+#[derive(Debug, Deserialize)]
+pub struct GeoJson {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub features: Vec<Feature>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Feature {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub properties: Properties,
+    pub geometry: Geometry,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Properties {
+    pub navn: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Geometry {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub coordinates: Coordinates,
+}
+
+/// Raw coordinate arrays for the geometry kinds we support. `Polygon` is a
+/// list of rings (first is the exterior, the rest are holes); `MultiPolygon`
+/// is a list of such ring-lists, one per disjoint piece.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum Coordinates {
+    Polygon(Vec<Vec<Vec<f64>>>),
+    MultiPolygon(Vec<Vec<Vec<Vec<f64>>>>),
+}
@@ -7,6 +7,7 @@ extern crate serde;
 extern crate serde_json;
 extern crate csv;
 extern crate geo;
+extern crate num;
 extern crate rustc_serialize;
 extern crate rayon;
 
@@ -17,27 +18,38 @@ include!("geojson.in.rs");
 include!(concat!(env!("OUT_DIR"), "/geojson.rs"));
 
 pub mod error;
+pub mod rtree;
+pub mod reader;
+pub mod writer;
+pub mod topojson;
 
 use rayon::prelude::*;
-use geo::{Point, Polygon, LineString};
+use geo::{Point, Polygon, MultiPolygon, LineString};
 use geo::algorithm::contains::Contains;
+use num::{Float, NumCast};
+use rustc_serialize::Decodable;
+use rtree::{BBox, RTree};
 use std::result;
 use std::fs::File;
 use std::io::prelude::*;
 
 pub type Result<T> = result::Result<T, error::Error>;
 
+/// A point record read from a CSV file. Generic over the coordinate
+/// precision `T` so memory-bound batch jobs can choose `f32` over the
+/// default `f64` when halving coordinate memory matters more than
+/// precision.
 #[derive(Debug, RustcDecodable)]
-pub struct Record {
+pub struct Record<T: Float + Send + Sync> {
     pub index: i32,
     pub testid: i64,
-    pub longitude: f64,
-    pub latitude: f64,
+    pub longitude: T,
+    pub latitude: T,
 }
 
-impl Record {
+impl<T: Float + Send + Sync> Record<T> {
     /// Returns a point with latitude and longitude (in that order).
-    pub fn position(&self) -> geo::Point<f64> {
+    pub fn position(&self) -> geo::Point<T> {
         Point::new(self.latitude, self.longitude)
     }
 }
@@ -50,31 +62,69 @@ fn test_record() {
 }
 
 #[derive(Debug)]
-pub struct County {
+pub struct County<T: Float + Send + Sync> {
     name: String,
-    poly: geo::Polygon<f64>,
+    poly: geo::MultiPolygon<T>,
+    /// `None` for a county whose polygon has no coordinates at all (a
+    /// degenerate but parseable geometry), in which case it's simply never
+    /// added to `Counties::index` and `lookup`/`lookup_record` fall straight
+    /// through to `poly.contains`, which will correctly report no match.
+    bbox: Option<BBox<T>>,
 }
 
-impl County {
+/// Cast a raw GeoJSON coordinate (always decoded as `f64`) down into the
+/// chosen precision `T`.
+fn cast<T: Float>(n: f64) -> T {
+    T::from(n).unwrap()
+}
+
+/// Turn a ring (list of `[lon, lat]` pairs) into a `LineString`.
+fn ring_to_linestring<T: Float>(ring: &[Vec<f64>]) -> LineString<T> {
+    LineString(ring.iter().map(|c| Point::new(cast(c[1]), cast(c[0]))).collect())
+}
+
+/// Turn a GeoJSON `Polygon` coordinate array (exterior ring followed by any
+/// number of interior rings) into a `Polygon` with holes.
+fn polygon_from_rings<T: Float>(rings: &[Vec<Vec<f64>>]) -> Polygon<T> {
+    let mut rings = rings.iter();
+    let exterior = rings.next().map(|r| ring_to_linestring(r)).unwrap_or(LineString(vec![]));
+    let holes = rings.map(|r| ring_to_linestring(r)).collect();
+    Polygon::new(exterior, holes)
+}
+
+impl<T: Float + Send + Sync> County<T> {
     /// Create a new County object from a Feature.
-    pub fn new(feat: &Feature) -> County {
-        let mut points = vec![];
-
-        // Extract the first/only array of coordinates (external borders)
-        let coords = feat.geometry.coordinates[0].clone();
-        for coord in coords {
-            let p = Point::new(coord[1].clone(), coord[0].clone());
-            points.push(p);
-        }
+    ///
+    /// Handles both `Polygon` and `MultiPolygon` geometries: the first ring
+    /// of each polygon is the exterior, the remaining rings become holes.
+    pub fn new(feat: &Feature) -> County<T> {
+        let polygons = match feat.geometry.coordinates {
+            Coordinates::Polygon(ref rings) => vec![polygon_from_rings(rings)],
+            Coordinates::MultiPolygon(ref polys) => {
+                polys.iter().map(|rings| polygon_from_rings(rings)).collect()
+            },
+        };
 
-        County {
-            name: feat.properties.navn.clone(),
-            poly: Polygon::new(LineString(points), vec![])
-        }
+        County::from_multipolygon(feat.properties.navn.clone(), MultiPolygon(polygons))
+    }
+
+    /// Build a County from an already-assembled MultiPolygon, e.g. one
+    /// reconstructed from TopoJSON arcs, computing its bounding box.
+    fn from_multipolygon(name: String, poly: MultiPolygon<T>) -> County<T> {
+        let bbox = BBox::from_points(poly.0.iter().flat_map(|p| {
+            p.exterior.0.iter().chain(p.interiors.iter().flat_map(|r| r.0.iter()))
+        }));
+
+        County { name: name, poly: poly, bbox: bbox }
     }
     /// Checks whether a point is in a county.
     /// Returns the name of the county.
-    pub fn lookup(&self, p: &geo::Point<f64>) -> Option<String> {
+    pub fn lookup(&self, p: &geo::Point<T>) -> Option<String> {
+        if let Some(ref bbox) = self.bbox {
+            if !bbox.contains(p) {
+                return None;
+            }
+        }
         match self.poly.contains(p) {
             true  => Some(self.name.clone()),
             false => None,
@@ -83,8 +133,14 @@ impl County {
 
     /// Lookup a record and return the testid and county if any.
     /// Returns a tuple with the testid and name of the county.
-    pub fn lookup_record(&self, p: &Record) -> Option<(i64, String)> {
-        match self.poly.contains(&p.position()) {
+    pub fn lookup_record(&self, p: &Record<T>) -> Option<(i64, String)> {
+        let point = p.position();
+        if let Some(ref bbox) = self.bbox {
+            if !bbox.contains(&point) {
+                return None;
+            }
+        }
+        match self.poly.contains(&point) {
             true  => Some((p.testid, self.name.clone())),
             false => None,
         }
@@ -99,27 +155,91 @@ fn test_county() {
     assert_eq!(res.lookup(&Point::new(60.524035, 5.552604)).unwrap(), "Osterøy");
 }
 
+/// A `MultiPolygon` county with a disjoint second piece (an island) and an
+/// interior-ring hole in its first piece.
+#[test]
+fn test_county_multipolygon_with_hole() {
+    let json = read_geojson("./examples/data/sample_multipolygon.geojson").unwrap();
+    let res  = County::new(&json.features[0]);
+    assert_eq!(res.name, "Fjellheim");
+    // inside the first polygon's exterior ring, outside its hole
+    assert_eq!(res.lookup(&Point::new(60.2, 5.2)).unwrap(), "Fjellheim");
+    // inside the hole -> not contained
+    assert!(res.lookup(&Point::new(61.0, 6.0)).is_none());
+    // inside the second, disjoint polygon
+    assert_eq!(res.lookup(&Point::new(65.5, 10.5)).unwrap(), "Fjellheim");
+}
+
 #[derive(Debug)]
-pub struct Counties {
-    list: Vec<County>,
+pub struct Counties<T: Float + Send + Sync> {
+    list: Vec<County<T>>,
+    index: RTree<T>,
 }
 
-impl Counties {
+impl<T: Float + Send + Sync> Counties<T> {
     /// Create a new Counties object from a GeoJson.
-    pub fn new(json: &GeoJson) -> Counties {
-        let mut counties: Vec<County> = vec![];
+    pub fn new(json: &GeoJson) -> Counties<T> {
+        let mut counties: Vec<County<T>> = vec![];
         for county in json.features.iter() {
             counties.push(County::new(county));
         }
+        let items = counties.iter().enumerate().filter_map(|(i, c)| c.bbox.map(|b| (b, i))).collect();
         Counties {
-            list: counties
+            index: RTree::new(items),
+            list: counties,
         }
     }
+
+    /// Build a Counties collection from any iterator of already-decoded
+    /// Features, e.g. `FeatureSeqReader::collect`'d and unwrapped.
+    pub fn from_features<I: IntoIterator<Item = Feature>>(features: I) -> Counties<T> {
+        let list: Vec<County<T>> = features.into_iter().map(|f| County::new(&f)).collect();
+        let items = list.iter().enumerate().filter_map(|(i, c)| c.bbox.map(|b| (b, i))).collect();
+        Counties {
+            index: RTree::new(items),
+            list: list,
+        }
+    }
+
+    /// Build a Counties collection from any iterator of `Result<Feature>`,
+    /// e.g. a `reader::FeatureReader` streaming from a large file on disk,
+    /// stopping at the first error instead of requiring the caller to drop
+    /// it with an adapter like `.filter_map(Result::ok)`.
+    pub fn from_feature_results<I: IntoIterator<Item = Result<Feature>>>(features: I) -> Result<Counties<T>> {
+        let mut list: Vec<County<T>> = vec![];
+        for feat in features {
+            list.push(County::new(&try!(feat)));
+        }
+        let items = list.iter().enumerate().filter_map(|(i, c)| c.bbox.map(|b| (b, i))).collect();
+        Ok(Counties {
+            index: RTree::new(items),
+            list: list,
+        })
+    }
+
+    /// Build a Counties collection from a TopoJSON `Topology`, decoding the
+    /// named object's arcs into the same MultiPolygons `Counties::new`
+    /// builds from GeoJSON.
+    pub fn from_topology(topo: &topojson::Topology, object: &str) -> Counties<T> {
+        let list: Vec<County<T>> = topo.polygons(object).into_iter()
+            .map(|(props, poly)| County::from_multipolygon(props.navn, poly))
+            .collect();
+        let items = list.iter().enumerate().filter_map(|(i, c)| c.bbox.map(|b| (b, i))).collect();
+        Counties {
+            index: RTree::new(items),
+            list: list,
+        }
+    }
+
     /// Lookup the county (if any) for a given point.
     /// Returns the name of the county.
-    pub fn lookup(&self, p: &geo::Point<f64>) -> Option<String> {
-        for kommune in self.list.iter() {
-            match kommune.lookup(p) {
+    ///
+    /// Queries the bbox index for candidate counties before falling back to
+    /// the full polygon `Contains` test, so this stays roughly logarithmic
+    /// instead of scanning every county.
+    pub fn lookup(&self, p: &geo::Point<T>) -> Option<String> {
+        for i in self.index.query(p) {
+            match self.list[i].lookup(p) {
                 Some(v) => {
                     return Some(v);
                 },
@@ -132,9 +252,9 @@ impl Counties {
 
     /// Lookup the county (if any) for a record.
     /// Returns a tuple with the name of the county and testid.
-    pub fn lookup_record(&self, p: &Record) -> Option<(i64, String)> {
-        for kommune in self.list.iter() {
-            match kommune.lookup_record(p) {
+    pub fn lookup_record(&self, p: &Record<T>) -> Option<(i64, String)> {
+        for i in self.index.query(&p.position()) {
+            match self.list[i].lookup_record(p) {
                 Some(v) => {
                     return Some(v);
                 },
@@ -146,14 +266,14 @@ impl Counties {
     }
 
     /// Lookup multiple locations in parallel.
-    pub fn lookup_all(&self, p: &Vec<geo::Point<f64>>) -> Vec<Option<String>> {
+    pub fn lookup_all(&self, p: &Vec<geo::Point<T>>) -> Vec<Option<String>> {
         let mut res = Vec::with_capacity(p.len());
         p.par_iter().map(|&point| self.lookup(&point)).collect_into(&mut res);
         res
     }
 
     /// Lookup multiple records in parallel.
-    pub fn lookup_all_records(&self, p: &Vec<Record>) -> Vec<Option<(i64, String)>> {
+    pub fn lookup_all_records(&self, p: &Vec<Record<T>>) -> Vec<Option<(i64, String)>> {
         let mut res = Vec::with_capacity(p.len());
         p.par_iter().map(|rec| self.lookup_record(&rec)).collect_into(&mut res);
         res
@@ -170,6 +290,43 @@ fn test_counties() {
     assert_eq!(res.lookup_all(&v)[0], Some("Osterøy".to_string()));
 }
 
+/// Two disjoint counties, exercising the R-tree's multi-leaf STR packing
+/// and traversal rather than the degenerate single-leaf case.
+#[test]
+fn test_counties_multi() {
+    let json = read_geojson("./examples/data/sample_counties.geojson").unwrap();
+    let res: Counties<f64> = Counties::new(&json);
+
+    assert_eq!(res.lookup(&Point::new(60.524035, 5.552604)).unwrap(), "Osterøy");
+    assert_eq!(res.lookup(&Point::new(40.5, 20.5)).unwrap(), "Bergen");
+
+    // Inside the bbox of neither leaf, but inside the root branch's union
+    // bbox, so this exercises the traversal rejecting both leaves rather
+    // than being rejected before the tree is even queried.
+    assert!(res.lookup(&Point::new(50.0, 13.0)).is_none());
+}
+
+/// `Counties::from_feature_results` is what actually lets a `FeatureReader`
+/// be fed straight into `Counties` without a `.filter_map(Result::ok)`
+/// adapter that would silently drop parse errors.
+#[test]
+fn test_from_feature_results() {
+    let file = File::open("./examples/data/sample.geojson").unwrap();
+    let reader = reader::FeatureReader::new(file).unwrap();
+    let res: Counties<f64> = Counties::from_feature_results(reader).unwrap();
+    assert_eq!(res.lookup(&Point::new(60.524035, 5.552604)).unwrap(), "Osterøy");
+}
+
+/// Exercises `Record`/`County`/`Counties` with `f32` coordinates; the other
+/// tests use the `f64` default.
+#[test]
+fn test_generic_precision_f32() {
+    let json = read_geojson("./examples/data/sample.geojson").unwrap();
+    let counties: Counties<f32> = Counties::new(&json);
+    let record = Record { index: 0, testid: 1000, longitude: 5.552604f32, latitude: 60.524035f32 };
+    assert_eq!(counties.lookup_record(&record).unwrap().1, "Osterøy");
+}
+
 /// Read the 'kommuner.geojson' file. The structure is predefined and should
 /// not be changed.
 ///
@@ -187,6 +344,61 @@ pub fn read_geojson(file: &str) -> Result<GeoJson> {
     Ok(res)
 }
 
+/// Read a TopoJSON file.
+///
+/// # Arguments
+/// `file`: A borrowed string with the path to the TopoJSON to be read.
+///
+/// # Returns
+/// A `Result` with a `topojson::Topology`.
+pub fn read_topojson(file: &str) -> Result<topojson::Topology> {
+    let mut f = try!(File::open(file));
+    let mut s = String::new();
+    try!(f.read_to_string(&mut s));
+    let res: topojson::Topology = try!(serde_json::from_str(&s));
+    Ok(res)
+}
+
+#[test]
+fn test_read_topojson() {
+    let res = read_topojson("./examples/data/sample.topojson");
+    match res {
+        Ok(topo) => {
+            let counties: Counties<f64> = Counties::from_topology(&topo, "kommuner");
+            assert_eq!(counties.lookup(&Point::new(60.524035, 5.552604)).unwrap(), "Osterøy");
+        },
+        Err(err) => panic!("Error: {:?}", err),
+    }
+}
+
+/// Read newline-delimited GeoJSON (GeoJSONSeq / JSON Lines), where each
+/// line is a standalone Feature rather than one big FeatureCollection.
+///
+/// # Arguments
+/// `file`: A borrowed string with the path to the file to be read.
+///
+/// # Returns
+/// A `Result` with a vector of Features, one per non-blank line.
+pub fn read_geojson_seq(file: &str) -> Result<Vec<Feature>> {
+    let f = try!(File::open(file));
+    let mut res = vec![];
+    for feat in reader::FeatureSeqReader::new(f) {
+        res.push(try!(feat));
+    }
+    Ok(res)
+}
+
+#[test]
+fn test_read_geojson_seq() {
+    let res = read_geojson_seq("./examples/data/sample.jsonl");
+    match res {
+        Ok(v)    => {
+            assert_eq!(v[0].properties.navn, "Osterøy");
+        },
+        Err(err) => panic!("Error: {:?}", err),
+    }
+}
+
 #[test]
 fn test_read_geojson() {
     let res = read_geojson("./examples/data/sample.geojson");
@@ -208,11 +420,11 @@ fn test_read_geojson() {
 /// # Returns
 /// A `Result` with a vector of records, where each record is a line in the CSV.
 ///
-pub fn read_csv(file: &str) -> Result<Vec<Record>> {
+pub fn read_csv<T: Float + Send + Sync + Decodable>(file: &str) -> Result<Vec<Record<T>>> {
     let mut csv = try!(csv::Reader::from_file(&file));
-    let mut res: Vec<Record> = vec![];
+    let mut res: Vec<Record<T>> = vec![];
     for line in csv.decode() {
-        let record: Record = try!(line);
+        let record: Record<T> = try!(line);
         res.push(record);
     }
     Ok(res)
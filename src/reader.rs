@@ -0,0 +1,248 @@
+//! Incremental reading of large GeoJSON inputs.
+//!
+//! `read_geojson` has to hold the whole file (and the whole
+//! `FeatureCollection`) in memory, which doesn't scale to multi-hundred-MB
+//! administrative boundary files. `FeatureReader` instead walks a
+//! `FeatureCollection`'s `"features"` array byte by byte and deserializes
+//! one `Feature` at a time, so peak memory stays proportional to a single
+//! feature rather than the whole file.
+
+use std::io::{BufRead, BufReader, Bytes, Lines, Read};
+use serde_json;
+
+use error::Error;
+use {Feature, Result};
+
+#[cfg(test)]
+use std::fs::File;
+
+/// Streams `Feature`s out of a `FeatureCollection` one at a time.
+pub struct FeatureReader<R: Read> {
+    bytes: Bytes<BufReader<R>>,
+    done: bool,
+}
+
+impl<R: Read> FeatureReader<R> {
+    /// Wrap `inner` and skip forward to the start of the `"features"` array.
+    pub fn new(inner: R) -> Result<FeatureReader<R>> {
+        let mut bytes = BufReader::new(inner).bytes();
+        try!(skip_to_features_array(&mut bytes));
+        Ok(FeatureReader { bytes: bytes, done: false })
+    }
+
+    /// Read the next balanced `{ ... }` feature object out of the array, if
+    /// any remain.
+    fn next_feature(&mut self) -> Result<Option<Feature>> {
+        loop {
+            match try!(next_byte(&mut self.bytes)) {
+                None => return Ok(None),
+                Some(b',') => continue,
+                Some(b) if (b as char).is_whitespace() => continue,
+                Some(b']') => return Ok(None),
+                Some(b'{') => {
+                    let mut raw = vec![b'{'];
+                    try!(read_balanced_object(&mut self.bytes, &mut raw));
+                    let feat: Feature = try!(serde_json::from_slice(&raw));
+                    return Ok(Some(feat));
+                },
+                Some(b) => {
+                    return Err(Error::Parse(format!("unexpected byte {} in features array", b)));
+                },
+            }
+        }
+    }
+}
+
+impl<R: Read> Iterator for FeatureReader<R> {
+    type Item = Result<Feature>;
+
+    fn next(&mut self) -> Option<Result<Feature>> {
+        if self.done {
+            return None;
+        }
+        match self.next_feature() {
+            Ok(Some(feat)) => Some(Ok(feat)),
+            Ok(None) => {
+                self.done = true;
+                None
+            },
+            Err(err) => {
+                self.done = true;
+                Some(Err(err))
+            },
+        }
+    }
+}
+
+/// Reads newline-delimited GeoJSON (the `jsonlines` / GeoJSONSeq
+/// convention): one standalone `Feature` per line, blank lines skipped.
+/// This is what many ETL tools emit instead of wrapping everything in a
+/// single `FeatureCollection`.
+pub struct FeatureSeqReader<R: Read> {
+    lines: Lines<BufReader<R>>,
+}
+
+impl<R: Read> FeatureSeqReader<R> {
+    pub fn new(inner: R) -> FeatureSeqReader<R> {
+        FeatureSeqReader { lines: BufReader::new(inner).lines() }
+    }
+}
+
+impl<R: Read> Iterator for FeatureSeqReader<R> {
+    type Item = Result<Feature>;
+
+    fn next(&mut self) -> Option<Result<Feature>> {
+        loop {
+            match self.lines.next() {
+                None => return None,
+                Some(Err(err)) => return Some(Err(Error::from(err))),
+                Some(Ok(ref line)) if line.trim().is_empty() => continue,
+                Some(Ok(line)) => return Some(serde_json::from_str(&line).map_err(Error::from)),
+            }
+        }
+    }
+}
+
+/// Read the next byte from the stream, if any.
+fn next_byte<R: Read>(bytes: &mut Bytes<BufReader<R>>) -> Result<Option<u8>> {
+    match bytes.next() {
+        Some(b) => Ok(Some(try!(b))),
+        None => Ok(None),
+    }
+}
+
+/// Advance `bytes` past the `FeatureCollection`'s opening `{"...":"...", "features": [`,
+/// leaving the cursor right after the array's opening bracket.
+///
+/// Tracks object/array nesting depth and, at the root object's depth (1),
+/// whether the next string is in key position (immediately after `{` or
+/// `,`) rather than value position (immediately after `:`). Only a
+/// depth-1 *key* equal to `"features"` matches, so a property whose
+/// *value* happens to be the string `"features"` (e.g. `"name":
+/// "features"`) can't be mistaken for the real key.
+fn skip_to_features_array<R: Read>(bytes: &mut Bytes<BufReader<R>>) -> Result<()> {
+    let mut depth = 0i32;
+    let mut expect_key = false;
+    loop {
+        match try!(next_byte(bytes)) {
+            None => return Err(Error::Parse("reached end of input before finding \"features\"".into())),
+            Some(b'{') => {
+                depth += 1;
+                if depth == 1 {
+                    expect_key = true;
+                }
+            },
+            Some(b'[') => depth += 1,
+            Some(b'}') | Some(b']') => depth -= 1,
+            Some(b',') => {
+                if depth == 1 {
+                    expect_key = true;
+                }
+            },
+            Some(b':') => {
+                if depth == 1 {
+                    expect_key = false;
+                }
+            },
+            Some(b'"') => {
+                let key = try!(read_json_string(bytes));
+                if depth == 1 && expect_key && key == "features" {
+                    break;
+                }
+            },
+            Some(_) => {},
+        }
+    }
+    // Skip the colon and whitespace up to (and including) the opening '['.
+    loop {
+        match try!(next_byte(bytes)) {
+            None => return Err(Error::Parse("reached end of input before the features array".into())),
+            Some(b'[') => return Ok(()),
+            Some(_) => continue,
+        }
+    }
+}
+
+/// Read the contents of a JSON string (the opening `"` has already been
+/// consumed), stopping at the closing unescaped `"`. Escape sequences are
+/// left un-decoded since callers only ever compare the result against plain
+/// ASCII key names.
+fn read_json_string<R: Read>(bytes: &mut Bytes<BufReader<R>>) -> Result<String> {
+    let mut buf = Vec::new();
+    let mut escaped = false;
+    loop {
+        let b = match try!(next_byte(bytes)) {
+            Some(b) => b,
+            None => return Err(Error::Parse("unexpected end of input inside a JSON string".into())),
+        };
+        if escaped {
+            escaped = false;
+            buf.push(b);
+            continue;
+        }
+        match b {
+            b'\\' => { escaped = true; buf.push(b); },
+            b'"' => break,
+            _ => buf.push(b),
+        }
+    }
+    Ok(String::from_utf8_lossy(&buf).into_owned())
+}
+
+/// Read a balanced `{ ... }` object (the leading `{` has already been
+/// consumed and pushed onto `buf`), tracking string literals so that braces
+/// inside string values don't throw off the depth count.
+fn read_balanced_object<R: Read>(bytes: &mut Bytes<BufReader<R>>, buf: &mut Vec<u8>) -> Result<()> {
+    let mut depth = 1;
+    let mut in_string = false;
+    let mut escaped = false;
+
+    while depth > 0 {
+        let b = match try!(next_byte(bytes)) {
+            Some(b) => b,
+            None => return Err(Error::Parse("unexpected end of input inside feature object".into())),
+        };
+        buf.push(b);
+
+        if in_string {
+            if escaped {
+                escaped = false;
+            } else if b == b'\\' {
+                escaped = true;
+            } else if b == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match b {
+            b'"' => in_string = true,
+            b'{' => depth += 1,
+            b'}' => depth -= 1,
+            _ => {},
+        }
+    }
+    Ok(())
+}
+
+#[test]
+fn test_feature_reader() {
+    let file = File::open("./examples/data/sample.geojson").unwrap();
+    let reader = FeatureReader::new(file).unwrap();
+    let features: Vec<Feature> = reader.map(|f| f.unwrap()).collect();
+    assert_eq!(features[0].properties.navn, "Osterøy");
+}
+
+/// A top-level property whose *value* is literally `"features"` (not the
+/// real key) must not be mistaken for the start of the features array.
+#[test]
+fn test_feature_reader_ignores_value_named_features() {
+    use std::io::Cursor;
+
+    let data = br#"{"type":"FeatureCollection","name":"features","bbox":[1,2,3,4],"features":[{"type":"Feature","properties":{"navn":"Osterøy"},"geometry":{"type":"Polygon","coordinates":[[[5.0,60.0],[6.0,60.0],[6.0,61.0],[5.0,61.0],[5.0,60.0]]]}}]}"#;
+
+    let reader = FeatureReader::new(Cursor::new(data.to_vec())).unwrap();
+    let features: Vec<Feature> = reader.map(|f| f.unwrap()).collect();
+    assert_eq!(features.len(), 1);
+    assert_eq!(features[0].properties.navn, "Osterøy");
+}
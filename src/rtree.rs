@@ -0,0 +1,154 @@
+//! A minimal bulk-loaded R-tree over axis-aligned bounding boxes.
+//!
+//! `Counties` uses this to narrow a point lookup down from every county to
+//! the handful whose bounding box could possibly contain the point, instead
+//! of running `Contains` against every polygon in the collection.
+
+use geo::Point;
+use num::Float;
+
+/// An axis-aligned bounding box, in the same (x, y) order as the rest of the
+/// crate (see `Record::position`).
+#[derive(Debug, Clone, Copy)]
+pub struct BBox<T: Float + Send + Sync> {
+    pub minx: T,
+    pub miny: T,
+    pub maxx: T,
+    pub maxy: T,
+}
+
+impl<T: Float + Send + Sync> BBox<T> {
+    /// Bounding box of a single point.
+    pub fn from_point(p: &Point<T>) -> BBox<T> {
+        BBox { minx: p.x(), miny: p.y(), maxx: p.x(), maxy: p.y() }
+    }
+
+    /// Bounding box enclosing every point yielded by `points`, or `None` if
+    /// `points` is empty (e.g. a `Polygon` feature with a degenerate,
+    /// coordinate-less ring).
+    pub fn from_points<'a, I>(mut points: I) -> Option<BBox<T>> where I: Iterator<Item = &'a Point<T>>, T: 'a {
+        let first = match points.next() {
+            Some(p) => p,
+            None => return None,
+        };
+        Some(points.fold(BBox::from_point(first), |acc, p| acc.union(&BBox::from_point(p))))
+    }
+
+    /// Smallest box containing both `self` and `other`.
+    pub fn union(&self, other: &BBox<T>) -> BBox<T> {
+        BBox {
+            minx: self.minx.min(other.minx),
+            miny: self.miny.min(other.miny),
+            maxx: self.maxx.max(other.maxx),
+            maxy: self.maxy.max(other.maxy),
+        }
+    }
+
+    /// Center of the box on the x axis, used to sort during STR bulk loading.
+    pub fn center_x(&self) -> T {
+        (self.minx + self.maxx) / (T::one() + T::one())
+    }
+
+    /// Center of the box on the y axis, used to sort during STR bulk loading.
+    pub fn center_y(&self) -> T {
+        (self.miny + self.maxy) / (T::one() + T::one())
+    }
+
+    /// Whether the box contains `p` (edges inclusive).
+    pub fn contains(&self, p: &Point<T>) -> bool {
+        p.x() >= self.minx && p.x() <= self.maxx && p.y() >= self.miny && p.y() <= self.maxy
+    }
+}
+
+/// Fan-out used when packing leaf and parent nodes.
+const FAN_OUT: usize = 16;
+
+#[derive(Debug, Clone)]
+enum Node<T: Float + Send + Sync> {
+    Leaf(BBox<T>, usize),
+    Branch(BBox<T>, Vec<Node<T>>),
+}
+
+impl<T: Float + Send + Sync> Node<T> {
+    fn bbox(&self) -> BBox<T> {
+        match *self {
+            Node::Leaf(b, _) => b,
+            Node::Branch(b, _) => b,
+        }
+    }
+}
+
+/// A sort-tile-recursive (STR) bulk-loaded R-tree. Each leaf carries the
+/// index of the item (e.g. a county in `Counties::list`) its box belongs to.
+/// `root` is `None` for an empty tree (e.g. zero counties, or a
+/// `Counties::from_topology` call against an unknown object name), in which
+/// case `query` always returns no matches instead of panicking.
+#[derive(Debug)]
+pub struct RTree<T: Float + Send + Sync> {
+    root: Option<Node<T>>,
+}
+
+impl<T: Float + Send + Sync> RTree<T> {
+    /// Build an R-tree from `(bbox, index)` pairs: sort by the x of the box
+    /// center, split into `ceil(sqrt(n))` vertical slices, sort each slice by
+    /// y, then pack leaves into branches of `FAN_OUT` bottom-up until a
+    /// single root remains. `items` may be empty.
+    pub fn new(mut items: Vec<(BBox<T>, usize)>) -> RTree<T> {
+        if items.is_empty() {
+            return RTree { root: None };
+        }
+
+        items.sort_by(|a, b| a.0.center_x().partial_cmp(&b.0.center_x()).unwrap());
+
+        let n = items.len();
+        let slice_count = (n as f64).sqrt().ceil() as usize;
+        let slice_size = (n + slice_count - 1) / slice_count;
+
+        let mut leaves: Vec<Node<T>> = Vec::with_capacity(n);
+        for slice in items.chunks(slice_size) {
+            let mut slice = slice.to_vec();
+            slice.sort_by(|a, b| a.0.center_y().partial_cmp(&b.0.center_y()).unwrap());
+            for &(bbox, idx) in slice.iter() {
+                leaves.push(Node::Leaf(bbox, idx));
+            }
+        }
+
+        RTree { root: Some(RTree::pack(leaves)) }
+    }
+
+    /// Group `nodes` into branches of `FAN_OUT` and recurse until one node
+    /// (the root) remains.
+    fn pack(nodes: Vec<Node<T>>) -> Node<T> {
+        if nodes.len() == 1 {
+            return nodes.into_iter().next().unwrap();
+        }
+        let parents: Vec<Node<T>> = nodes.chunks(FAN_OUT).map(|chunk| {
+            let bbox = chunk[1..].iter().fold(chunk[0].bbox(), |acc, n| acc.union(&n.bbox()));
+            Node::Branch(bbox, chunk.to_vec())
+        }).collect();
+        RTree::pack(parents)
+    }
+
+    /// Return the indices of every leaf whose bounding box contains `p`.
+    pub fn query(&self, p: &Point<T>) -> Vec<usize> {
+        let mut out = vec![];
+        if let Some(ref root) = self.root {
+            RTree::query_node(root, p, &mut out);
+        }
+        out
+    }
+
+    fn query_node(node: &Node<T>, p: &Point<T>, out: &mut Vec<usize>) {
+        if !node.bbox().contains(p) {
+            return;
+        }
+        match *node {
+            Node::Leaf(_, idx) => out.push(idx),
+            Node::Branch(_, ref children) => {
+                for child in children {
+                    RTree::query_node(child, p, out);
+                }
+            },
+        }
+    }
+}
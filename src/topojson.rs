@@ -0,0 +1,127 @@
+//! Reading TopoJSON boundary files.
+//!
+//! TopoJSON stores shared borders once as quantized "arcs" referenced by
+//! index instead of repeating every ring's coordinates per polygon the way
+//! GeoJSON does, which makes it far smaller for adjacent administrative
+//! polygons like counties. This module dequantizes the arcs and
+//! reconstructs the rings so the rest of the crate can work with the same
+//! `geo::Polygon`/`MultiPolygon` types it already builds from GeoJSON.
+
+use std::collections::HashMap;
+use geo::{Point, Polygon, MultiPolygon, LineString};
+use num::Float;
+
+use Properties;
+
+#[derive(Debug, Deserialize)]
+pub struct Topology {
+    pub transform: Transform,
+    pub objects: HashMap<String, GeometryCollection>,
+    pub arcs: Vec<Vec<[f64; 2]>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Transform {
+    pub scale: [f64; 2],
+    pub translate: [f64; 2],
+}
+
+#[derive(Debug, Deserialize)]
+pub struct GeometryCollection {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub geometries: Vec<TopoGeometry>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TopoGeometry {
+    #[serde(rename = "type")]
+    pub kind: String,
+    pub properties: Properties,
+    pub arcs: TopoArcs,
+}
+
+/// Arc indices for a geometry's rings: `Polygon` is a list of rings, each a
+/// list of arc indices (first ring is the exterior); `MultiPolygon` is a
+/// list of such ring-lists, one per disjoint piece.
+#[derive(Debug, Deserialize)]
+#[serde(untagged)]
+pub enum TopoArcs {
+    Polygon(Vec<Vec<i64>>),
+    MultiPolygon(Vec<Vec<Vec<i64>>>),
+}
+
+/// Cumulatively delta-decode one quantized arc into absolute `[lon, lat]`
+/// pairs using the topology's `transform`.
+fn decode_arc(raw: &[[f64; 2]], transform: &Transform) -> Vec<[f64; 2]> {
+    let mut x = 0.0;
+    let mut y = 0.0;
+    raw.iter().map(|d| {
+        x += d[0];
+        y += d[1];
+        [x * transform.scale[0] + transform.translate[0], y * transform.scale[1] + transform.translate[1]]
+    }).collect()
+}
+
+/// Reconstruct a ring from its arc indices. A negative index `~i` (the
+/// bitwise complement of `i`) means arc `i` traversed in reverse; the
+/// shared endpoint between consecutive arcs is de-duplicated.
+fn ring_from_arcs(indices: &[i64], decoded: &[Vec<[f64; 2]>]) -> Vec<[f64; 2]> {
+    let mut points: Vec<[f64; 2]> = vec![];
+    for &idx in indices {
+        let (i, reversed) = if idx < 0 { ((!idx) as usize, true) } else { (idx as usize, false) };
+        let mut arc = decoded[i].clone();
+        if reversed {
+            arc.reverse();
+        }
+        if points.is_empty() {
+            points.extend(arc);
+        } else {
+            // The first point of this arc duplicates the last point of
+            // the previous one; drop it.
+            points.extend(arc.into_iter().skip(1));
+        }
+    }
+    points
+}
+
+fn ring_to_linestring<T: Float>(ring: &[[f64; 2]]) -> LineString<T> {
+    LineString(ring.iter().map(|c| Point::new(T::from(c[1]).unwrap(), T::from(c[0]).unwrap())).collect())
+}
+
+/// Reconstruct a `Polygon` (exterior ring followed by any holes) from a
+/// list of arc-index rings.
+fn polygon_from_rings<T: Float>(rings: &[Vec<i64>], decoded: &[Vec<[f64; 2]>]) -> Polygon<T> {
+    let mut rings = rings.iter().map(|r| ring_from_arcs(r, decoded));
+    let exterior = rings.next().map(|r| ring_to_linestring(&r)).unwrap_or(LineString(vec![]));
+    let holes = rings.map(|r| ring_to_linestring(&r)).collect();
+    Polygon::new(exterior, holes)
+}
+
+impl Topology {
+    /// Decode every arc once up front so individual geometries can share
+    /// the work of dequantizing borders they have in common.
+    fn decode_arcs(&self) -> Vec<Vec<[f64; 2]>> {
+        self.arcs.iter().map(|arc| decode_arc(arc, &self.transform)).collect()
+    }
+
+    /// Build a `(Properties, MultiPolygon)` pair for every geometry in the
+    /// named object, in the same shape `County::new` builds from GeoJSON.
+    pub fn polygons<T: Float>(&self, object: &str) -> Vec<(Properties, MultiPolygon<T>)> {
+        let decoded = self.decode_arcs();
+        let collection = match self.objects.get(object) {
+            Some(c) => c,
+            None => return vec![],
+        };
+
+        collection.geometries.iter().map(|geom| {
+            let polygons = match geom.arcs {
+                TopoArcs::Polygon(ref rings) => vec![polygon_from_rings(rings, &decoded)],
+                TopoArcs::MultiPolygon(ref polys) => {
+                    polys.iter().map(|rings| polygon_from_rings(rings, &decoded)).collect()
+                },
+            };
+            (Properties { navn: geom.properties.navn.clone() }, MultiPolygon(polygons))
+        }).collect()
+    }
+}
@@ -0,0 +1,119 @@
+//! Writing lookup results back out as GeoJSON.
+//!
+//! `Counties::lookup_all_records` only hands back bare `(testid, county)`
+//! tuples, so there was no way to get a geospatial file back out of a
+//! lookup run. `ToFeature`/`ToFeatureCollection` turn matched records into
+//! a `FeatureCollection` that can be serialized straight to disk and loaded
+//! into QGIS or a web map.
+
+use std::fs::File;
+use std::io::Write;
+use serde_json;
+use num::{Float, ToPrimitive};
+
+use {Record, Result};
+
+#[derive(Debug, Serialize)]
+pub struct OutputGeometry {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    coordinates: [f64; 2],
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutputProperties {
+    testid: i64,
+    county: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutputFeature {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    properties: OutputProperties,
+    geometry: OutputGeometry,
+}
+
+#[derive(Debug, Serialize)]
+pub struct OutputFeatureCollection {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    features: Vec<OutputFeature>,
+}
+
+/// Turns a value into a single GeoJSON `Feature`.
+pub trait ToFeature {
+    fn to_feature(&self, county: &str) -> OutputFeature;
+}
+
+impl<T: Float + Send + Sync> ToFeature for Record<T> {
+    fn to_feature(&self, county: &str) -> OutputFeature {
+        OutputFeature {
+            kind: "Feature",
+            properties: OutputProperties { testid: self.testid, county: county.to_string() },
+            geometry: OutputGeometry {
+                kind: "Point",
+                coordinates: [self.longitude.to_f64().unwrap(), self.latitude.to_f64().unwrap()],
+            },
+        }
+    }
+}
+
+/// Turns a collection of matched records into a GeoJSON
+/// `FeatureCollection`.
+pub trait ToFeatureCollection {
+    fn to_feature_collection(&self) -> OutputFeatureCollection;
+}
+
+impl<'a, T: Float + Send + Sync> ToFeatureCollection for [(&'a Record<T>, String)] {
+    fn to_feature_collection(&self) -> OutputFeatureCollection {
+        OutputFeatureCollection {
+            kind: "FeatureCollection",
+            features: self.iter().map(|&(rec, ref county)| rec.to_feature(county)).collect(),
+        }
+    }
+}
+
+/// Pair each record up with its matched county (records with no match are
+/// dropped) and serialize the result to `file` as a GeoJSON
+/// `FeatureCollection`.
+pub fn write_matches<T: Float + Send + Sync>(records: &[Record<T>], matches: &[Option<(i64, String)>], file: &str) -> Result<()> {
+    let pairs: Vec<(&Record<T>, String)> = records.iter().zip(matches.iter())
+        .filter_map(|(rec, m)| m.as_ref().map(|&(_, ref county)| (rec, county.clone())))
+        .collect();
+
+    let collection = pairs.to_feature_collection();
+    let mut f = try!(File::create(file));
+    let s = try!(serde_json::to_string(&collection));
+    try!(f.write_all(s.as_bytes()));
+    Ok(())
+}
+
+#[test]
+fn test_write_matches() {
+    use std::env::temp_dir;
+    use std::io::Read;
+
+    let records = vec![
+        Record { index: 0, testid: 1000, longitude: 5.552604, latitude: 60.524035 },
+        Record { index: 1, testid: 1001, longitude: 10.7522, latitude: 59.9139 },
+    ];
+    let matches = vec![
+        Some((1000, "Osterøy".to_string())),
+        None,
+    ];
+
+    let mut path = temp_dir();
+    path.push("rustgeojson-test-write-matches.geojson");
+    let file = path.to_str().unwrap();
+
+    write_matches(&records, &matches, file).unwrap();
+
+    let mut s = String::new();
+    File::open(file).unwrap().read_to_string(&mut s).unwrap();
+    let collection: serde_json::Value = serde_json::from_str(&s).unwrap();
+
+    assert_eq!(collection["type"], "FeatureCollection");
+    assert_eq!(collection["features"].as_array().unwrap().len(), 1);
+    assert_eq!(collection["features"][0]["properties"]["county"], "Osterøy");
+}